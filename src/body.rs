@@ -0,0 +1,70 @@
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
+
+/// A streaming request/response body.
+pub type BodyStream = Box<Stream<Item = Bytes, Error = io::Error>>;
+
+/// The various shapes a request/response body can take.
+pub enum Body {
+    /// Empty body, zero length.
+    Empty,
+    /// An in-memory body with a known length.
+    Binary(Binary),
+    /// A streaming body of unknown length.
+    Streaming(BodyStream),
+    /// A body driven by an actor.
+    Actor(BodyStream),
+}
+
+impl Body {
+    #[inline]
+    pub fn is_binary(&self) -> bool {
+        match *self {
+            Body::Binary(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// An in-memory chunk of body bytes, cheap to clone.
+#[derive(Clone, Debug)]
+pub struct Binary(Bytes);
+
+impl Binary {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl AsRef<[u8]> for Binary {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl From<Bytes> for Binary {
+    fn from(data: Bytes) -> Binary {
+        Binary(data)
+    }
+}
+
+impl From<BytesMut> for Binary {
+    fn from(data: BytesMut) -> Binary {
+        Binary(data.freeze())
+    }
+}
+
+impl From<Vec<u8>> for Binary {
+    fn from(data: Vec<u8>) -> Binary {
+        Binary(Bytes::from(data))
+    }
+}
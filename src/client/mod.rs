@@ -0,0 +1,5 @@
+mod request;
+pub(crate) mod writer;
+
+pub use self::request::{ClientRequest, ClientRequestBuilder};
+pub use self::writer::{CompressionLevel, WriteObserver};
@@ -0,0 +1,152 @@
+use http::{HeaderMap, Method, Uri, Version};
+
+use body::Body;
+use client::writer::{CompressionLevel, WriteObserver};
+use headers::ContentEncoding;
+
+/// An outgoing HTTP request built by a client.
+pub struct ClientRequest {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    body: Body,
+    chunked: bool,
+    upgrade: bool,
+    encoding: ContentEncoding,
+    encoding_level: CompressionLevel,
+    observer: Option<Box<WriteObserver>>,
+}
+
+impl ClientRequest {
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn replace_body(&mut self, body: Body) -> Body {
+        ::std::mem::replace(&mut self.body, body)
+    }
+
+    pub fn chunked(&self) -> bool {
+        self.chunked
+    }
+
+    pub fn upgrade(&self) -> bool {
+        self.upgrade
+    }
+
+    pub fn content_encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    /// The compression level to use when `content_encoding()` requires compression.
+    pub fn content_encoding_level(&self) -> CompressionLevel {
+        self.encoding_level
+    }
+
+    /// Take the observer set via `ClientRequestBuilder::with_observer`, if any.
+    pub fn take_observer(&mut self) -> Option<Box<WriteObserver>> {
+        self.observer.take()
+    }
+}
+
+/// Builds a `ClientRequest`.
+pub struct ClientRequestBuilder {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    body: Body,
+    chunked: bool,
+    upgrade: bool,
+    encoding: ContentEncoding,
+    encoding_level: CompressionLevel,
+    observer: Option<Box<WriteObserver>>,
+}
+
+impl ClientRequestBuilder {
+    pub fn new(method: Method, uri: Uri) -> ClientRequestBuilder {
+        ClientRequestBuilder {
+            method,
+            uri,
+            version: Version::HTTP_11,
+            headers: HeaderMap::new(),
+            body: Body::Empty,
+            chunked: false,
+            upgrade: false,
+            encoding: ContentEncoding::Auto,
+            encoding_level: CompressionLevel::Default,
+            observer: None,
+        }
+    }
+
+    pub fn method(&mut self, method: Method) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    pub fn uri(&mut self, uri: Uri) -> &mut Self {
+        self.uri = uri;
+        self
+    }
+
+    pub fn content_encoding(&mut self, encoding: ContentEncoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the compression level used for `content_encoding()`. Has no effect
+    /// when the chosen encoding doesn't compress (`Identity`, or `Auto`
+    /// resolving to `Identity`).
+    pub fn content_encoding_level(&mut self, level: CompressionLevel) -> &mut Self {
+        self.encoding_level = level;
+        self
+    }
+
+    pub fn body(&mut self, body: Body) -> &mut Self {
+        self.body = body;
+        self
+    }
+
+    /// Register an observer to be notified of write progress and state
+    /// changes while this request is sent.
+    pub fn with_observer(&mut self, observer: Box<WriteObserver>) -> &mut Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn finish(&mut self) -> ClientRequest {
+        ClientRequest {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            version: self.version,
+            headers: ::std::mem::replace(&mut self.headers, HeaderMap::new()),
+            body: ::std::mem::replace(&mut self.body, Body::Empty),
+            chunked: self.chunked,
+            upgrade: self.upgrade,
+            encoding: self.encoding,
+            encoding_level: self.encoding_level,
+            observer: self.observer.take(),
+        }
+    }
+}
@@ -9,10 +9,11 @@ use futures::{Async, Poll};
 use tokio_io::AsyncWrite;
 use http::{Version, HttpTryFrom};
 use http::header::{HeaderValue, DATE,
-                   CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+                   CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
 use flate2::Compression;
 use flate2::write::{GzEncoder, DeflateEncoder};
 use brotli2::write::BrotliEncoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
 
 use body::{Body, Binary};
 use headers::ContentEncoding;
@@ -27,6 +28,59 @@ const LOW_WATERMARK: usize = 1024;
 const HIGH_WATERMARK: usize = 8 * LOW_WATERMARK;
 const AVERAGE_HEADER_SIZE: usize = 30;
 
+/// Compression effort to use when a request's `ContentEncoding` compresses
+/// the body. `Default`/`Fastest`/`Best` map onto each encoder's own notion
+/// of those tradeoffs; `Level(n)` is clamped to the encoder's valid range.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompressionLevel {
+    Default,
+    Fastest,
+    Best,
+    Level(u32),
+}
+
+impl CompressionLevel {
+    fn flate2(self) -> Compression {
+        match self {
+            CompressionLevel::Default => Compression::default(),
+            CompressionLevel::Fastest => Compression::fast(),
+            CompressionLevel::Best => Compression::best(),
+            CompressionLevel::Level(n) => Compression::new(n.min(9)),
+        }
+    }
+
+    fn brotli(self) -> u32 {
+        match self {
+            CompressionLevel::Default => 5,
+            CompressionLevel::Fastest => 1,
+            CompressionLevel::Best => 11,
+            CompressionLevel::Level(n) => n.min(11),
+        }
+    }
+
+    fn zstd(self) -> i32 {
+        match self {
+            CompressionLevel::Default => 3,
+            CompressionLevel::Fastest => 1,
+            CompressionLevel::Best => 21,
+            CompressionLevel::Level(n) => n.min(21) as i32,
+        }
+    }
+}
+
+/// Observes write activity on an `HttpClientWriter` — e.g. to drive an
+/// upload progress bar, rate-limit, or collect throughput metrics.
+pub trait WriteObserver: Send {
+    /// Called each time bytes are actually flushed to the underlying
+    /// stream. `total_written` is the cumulative count flushed so far.
+    fn on_write(&self, _n: usize, _total_written: u64) {}
+
+    /// Called from `poll_completed` when the state reached after flushing
+    /// differs from the last one reported. The state returned by `write()`
+    /// is not reported.
+    fn on_state(&self, _state: WriterState) {}
+}
+
 bitflags! {
     struct Flags: u8 {
         const STARTED = 0b0000_0001;
@@ -39,11 +93,14 @@ bitflags! {
 pub(crate) struct HttpClientWriter {
     flags: Flags,
     written: u64,
+    flushed: u64,
     headers_size: u32,
     buffer: SharedBytes,
     encoder: ContentEncoder,
     low: usize,
     high: usize,
+    observer: Option<Box<WriteObserver>>,
+    last_state: Option<WriterState>,
 }
 
 impl HttpClientWriter {
@@ -53,11 +110,14 @@ impl HttpClientWriter {
         HttpClientWriter {
             flags: Flags::empty(),
             written: 0,
+            flushed: 0,
             headers_size: 0,
             buffer: buf,
             encoder: encoder,
             low: LOW_WATERMARK,
             high: HIGH_WATERMARK,
+            observer: None,
+            last_state: None,
         }
     }
 
@@ -75,6 +135,24 @@ impl HttpClientWriter {
         self.high = high_watermark;
     }
 
+    /// Total bytes actually flushed to the underlying stream.
+    pub fn bytes_written(&self) -> u64 {
+        self.flushed
+    }
+
+    pub fn set_observer(&mut self, observer: Box<WriteObserver>) {
+        self.observer = Some(observer);
+    }
+
+    fn notify_state(&mut self, state: WriterState) {
+        if self.last_state != Some(state) {
+            self.last_state = Some(state);
+            if let Some(ref observer) = self.observer {
+                observer.on_state(state);
+            }
+        }
+    }
+
     fn write_to_stream<T: AsyncWrite>(&mut self, stream: &mut T) -> io::Result<WriterState> {
         while !self.buffer.is_empty() {
             match stream.write(self.buffer.as_ref()) {
@@ -84,6 +162,10 @@ impl HttpClientWriter {
                 },
                 Ok(n) => {
                     let _ = self.buffer.split_to(n);
+                    self.flushed += n as u64;
+                    if let Some(ref observer) = self.observer {
+                        observer.on_write(n, self.flushed);
+                    }
                 },
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                     if self.buffer.len() > self.high {
@@ -104,7 +186,10 @@ impl HttpClientWriter {
     pub fn start(&mut self, msg: &mut ClientRequest) -> io::Result<()> {
         // prepare task
         self.flags.insert(Flags::STARTED);
-        self.encoder = content_encoder(self.buffer.clone(), msg);
+        self.encoder = content_encoder(self.buffer.clone(), msg)?;
+        if let Some(observer) = msg.take_observer() {
+            self.observer = Some(observer);
+        }
 
         // render message
         {
@@ -187,47 +272,104 @@ impl HttpClientWriter {
                                          -> Poll<(), io::Error>
     {
         match self.write_to_stream(stream) {
-            Ok(WriterState::Done) => {
+            Ok(state @ WriterState::Done) => {
+                self.notify_state(state);
                 if shutdown {
                     stream.shutdown()
                 } else {
                     Ok(Async::Ready(()))
                 }
             },
-            Ok(WriterState::Pause) => Ok(Async::NotReady),
+            Ok(state @ WriterState::Pause) => {
+                self.notify_state(state);
+                Ok(Async::NotReady)
+            },
             Err(err) => Err(err)
         }
     }
 }
 
 
-fn content_encoder(buf: SharedBytes, req: &mut ClientRequest) -> ContentEncoder {
+fn build_encoder(encoding: ContentEncoding, level: CompressionLevel, transfer: TransferEncoding)
+                  -> io::Result<ContentEncoder> {
+    Ok(match encoding {
+        ContentEncoding::Deflate => ContentEncoder::Deflate(
+            DeflateEncoder::new(transfer, level.flate2())),
+        ContentEncoding::Gzip => ContentEncoder::Gzip(
+            GzEncoder::new(transfer, level.flate2())),
+        ContentEncoding::Br => ContentEncoder::Br(
+            BrotliEncoder::new(transfer, level.brotli())),
+        ContentEncoding::Zstd => {
+            match ZstdEncoder::new(transfer, level.zstd()) {
+                Ok(enc) => ContentEncoder::Zstd(enc),
+                Err(err) => {
+                    error!("failed to construct zstd encoder: {}", err);
+                    return Err(err);
+                }
+            }
+        }
+        ContentEncoding::Identity | ContentEncoding::Auto => ContentEncoder::Identity(transfer),
+    })
+}
+
+// Below this size the framing overhead of compression tends to outweigh
+// the savings, so `Auto` leaves small bodies uncompressed.
+const AUTO_COMPRESSION_THRESHOLD: u64 = 1024;
+
+// Content-types that are already compressed (images, video, archives, ...);
+// `Auto` skips compressing these rather than spend cycles for no gain.
+const AUTO_INCOMPRESSIBLE_TYPES: &[&str] = &[
+    "image/", "video/", "audio/",
+    "application/zip", "application/gzip", "application/x-7z-compressed",
+    "application/x-rar-compressed", "application/x-bzip2",
+];
+
+fn content_length_hint(header: Option<&HeaderValue>) -> Option<u64> {
+    header
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+}
+
+/// Resolve `ContentEncoding::Auto` to a concrete encoding. `len` is a size
+/// hint for the body (exact for `Body::Binary`, taken from `Content-Length`
+/// for streaming bodies); `None` means the size isn't known up front.
+fn auto_encoding(len: Option<u64>, content_type: Option<&HeaderValue>) -> ContentEncoding {
+    if let Some(len) = len {
+        if len < AUTO_COMPRESSION_THRESHOLD {
+            return ContentEncoding::Identity;
+        }
+    }
+    if let Some(ct) = content_type.and_then(|v| v.to_str().ok()) {
+        let ct = ct.to_lowercase();
+        if AUTO_INCOMPRESSIBLE_TYPES.iter().any(|prefix| ct.starts_with(prefix)) {
+            return ContentEncoding::Identity;
+        }
+    }
+    ContentEncoding::Gzip
+}
+
+fn content_encoder(buf: SharedBytes, req: &mut ClientRequest) -> io::Result<ContentEncoder> {
     let version = req.version();
+    let level = req.content_encoding_level();
     let mut body = req.replace_body(Body::Empty);
     let mut encoding = req.content_encoding();
 
     let transfer = match body {
         Body::Empty => {
+            encoding = ContentEncoding::Identity;
             req.headers_mut().remove(CONTENT_LENGTH);
             TransferEncoding::length(0, buf)
         },
         Body::Binary(ref mut bytes) => {
+            if encoding == ContentEncoding::Auto {
+                encoding = auto_encoding(Some(bytes.len() as u64), req.headers().get(CONTENT_TYPE));
+            }
             if encoding.is_compression() {
                 let tmp = SharedBytes::default();
                 let transfer = TransferEncoding::eof(tmp.clone());
-                let mut enc = match encoding {
-                    ContentEncoding::Deflate => ContentEncoder::Deflate(
-                        DeflateEncoder::new(transfer, Compression::default())),
-                    ContentEncoding::Gzip => ContentEncoder::Gzip(
-                        GzEncoder::new(transfer, Compression::default())),
-                    ContentEncoding::Br => ContentEncoder::Br(
-                        BrotliEncoder::new(transfer, 5)),
-                    ContentEncoding::Identity => ContentEncoder::Identity(transfer),
-                    ContentEncoding::Auto => unreachable!()
-                };
-                // TODO return error!
-                let _ = enc.write(bytes.clone());
-                let _ = enc.write_eof();
+                let mut enc = build_encoder(encoding, level, transfer)?;
+                enc.write(bytes.clone())?;
+                enc.write_eof()?;
                 *bytes = Binary::from(tmp.take());
 
                 req.headers_mut().insert(
@@ -241,6 +383,10 @@ fn content_encoder(buf: SharedBytes, req: &mut ClientRequest) -> ContentEncoder
             TransferEncoding::eof(buf)
         },
         Body::Streaming(_) | Body::Actor(_) => {
+            if encoding == ContentEncoding::Auto {
+                let hint = content_length_hint(req.headers().get(CONTENT_LENGTH));
+                encoding = auto_encoding(hint, req.headers().get(CONTENT_TYPE));
+            }
             if req.upgrade() {
                 if version == Version::HTTP_2 {
                     error!("Connection upgrade is forbidden for HTTP/2");
@@ -264,15 +410,7 @@ fn content_encoder(buf: SharedBytes, req: &mut ClientRequest) -> ContentEncoder
     }
 
     req.replace_body(body);
-    match encoding {
-        ContentEncoding::Deflate => ContentEncoder::Deflate(
-            DeflateEncoder::new(transfer, Compression::default())),
-        ContentEncoding::Gzip => ContentEncoder::Gzip(
-            GzEncoder::new(transfer, Compression::default())),
-        ContentEncoding::Br => ContentEncoder::Br(
-            BrotliEncoder::new(transfer, 5)),
-        ContentEncoding::Identity | ContentEncoding::Auto => ContentEncoder::Identity(transfer),
-    }
+    build_encoder(encoding, level, transfer)
 }
 
 fn streaming_encoding(buf: SharedBytes, version: Version, req: &mut ClientRequest)
@@ -335,6 +473,12 @@ fn streaming_encoding(buf: SharedBytes, version: Version, req: &mut ClientReques
 // "Sun, 06 Nov 1994 08:49:37 GMT".len()
 pub const DATE_VALUE_LENGTH: usize = 29;
 
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// The cache is thread-local, so this must be invoked from the thread
+// actually driving the connection, not from some other worker.
 fn set_date(dst: &mut BytesMut) {
     CACHED.with(|cache| {
         let mut cache = cache.borrow_mut();
@@ -346,6 +490,15 @@ fn set_date(dst: &mut BytesMut) {
     })
 }
 
+/// Override how long a cached Date header value is reused before being
+/// recomputed. Set to `Duration::zero()` (e.g. in tests) to recompute on
+/// every call; applies only to the calling thread's cache.
+pub fn set_date_cache_interval(interval: Duration) {
+    CACHE_INTERVAL.with(|i| *i.borrow_mut() = interval);
+}
+
+thread_local!(static CACHE_INTERVAL: RefCell<Duration> = RefCell::new(Duration::seconds(1)));
+
 struct CachedDate {
     bytes: [u8; DATE_VALUE_LENGTH],
     next_update: time::Timespec,
@@ -362,8 +515,173 @@ impl CachedDate {
     }
 
     fn update(&mut self, now: time::Timespec) {
-        write!(&mut self.bytes[..], "{}", time::at_utc(now).rfc822()).unwrap();
-        self.next_update = now + Duration::seconds(1);
+        let tm = time::at_utc(now);
+        // Format by hand rather than via `Tm::rfc822()`, whose "%Z" can
+        // render as "UTC" (or be empty) depending on platform/locale
+        // instead of the "GMT" HTTP requires.
+        write!(&mut self.bytes[..], "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+               WEEKDAYS[tm.tm_wday as usize], tm.tm_mday, MONTHS[tm.tm_mon as usize],
+               1900 + tm.tm_year, tm.tm_hour, tm.tm_min, tm.tm_sec).unwrap();
+
+        let interval = CACHE_INTERVAL.with(|i| *i.borrow());
+        self.next_update = now + interval;
         self.next_update.nsec = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_level_flate2_clamps_to_valid_range() {
+        assert_eq!(CompressionLevel::Default.flate2().level(), Compression::default().level());
+        assert_eq!(CompressionLevel::Fastest.flate2().level(), Compression::fast().level());
+        assert_eq!(CompressionLevel::Best.flate2().level(), Compression::best().level());
+        assert_eq!(CompressionLevel::Level(100).flate2().level(), 9);
+    }
+
+    #[test]
+    fn compression_level_brotli_clamps_to_valid_range() {
+        assert_eq!(CompressionLevel::Default.brotli(), 5);
+        assert_eq!(CompressionLevel::Fastest.brotli(), 1);
+        assert_eq!(CompressionLevel::Best.brotli(), 11);
+        assert_eq!(CompressionLevel::Level(100).brotli(), 11);
+    }
+
+    #[test]
+    fn auto_encoding_skips_small_bodies() {
+        assert_eq!(auto_encoding(Some(10), None), ContentEncoding::Identity);
+    }
+
+    #[test]
+    fn auto_encoding_compresses_large_bodies() {
+        assert_eq!(auto_encoding(Some(AUTO_COMPRESSION_THRESHOLD), None), ContentEncoding::Gzip);
+    }
+
+    #[test]
+    fn auto_encoding_compresses_unknown_size() {
+        // no size hint (e.g. a streaming body without Content-Length) is
+        // treated as possibly-large and compressed by default
+        assert_eq!(auto_encoding(None, None), ContentEncoding::Gzip);
+    }
+
+    #[test]
+    fn auto_encoding_skips_incompressible_content_type() {
+        let ct = HeaderValue::from_static("image/png");
+        assert_eq!(auto_encoding(Some(1_000_000), Some(&ct)), ContentEncoding::Identity);
+    }
+
+    #[test]
+    fn auto_encoding_content_type_match_is_case_insensitive() {
+        let ct = HeaderValue::from_static("IMAGE/PNG");
+        assert_eq!(auto_encoding(Some(1_000_000), Some(&ct)), ContentEncoding::Identity);
+    }
+
+    #[test]
+    fn compression_level_zstd_clamps_to_valid_range() {
+        assert_eq!(CompressionLevel::Default.zstd(), 3);
+        assert_eq!(CompressionLevel::Fastest.zstd(), 1);
+        assert_eq!(CompressionLevel::Best.zstd(), 21);
+        assert_eq!(CompressionLevel::Level(100).zstd(), 21);
+    }
+
+    use std::sync::{Arc, Mutex};
+
+    /// Accepts at most `limit` bytes per `write()` call, to simulate a
+    /// backpressured socket that flushes in several chunks.
+    struct ChunkedStream {
+        limit: usize,
+    }
+
+    impl io::Write for ChunkedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len().min(self.limit))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for ChunkedStream {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        writes: Mutex<Vec<(usize, u64)>>,
+        states: Mutex<Vec<WriterState>>,
+    }
+
+    impl WriteObserver for Arc<RecordingObserver> {
+        fn on_write(&self, n: usize, total_written: u64) {
+            self.writes.lock().unwrap().push((n, total_written));
+        }
+
+        fn on_state(&self, state: WriterState) {
+            self.states.lock().unwrap().push(state);
+        }
+    }
+
+    #[test]
+    fn write_to_stream_reports_cumulative_flushed_bytes() {
+        let mut writer = HttpClientWriter::new(SharedBytes::default());
+        let observer = Arc::new(RecordingObserver::default());
+        writer.set_observer(Box::new(observer.clone()));
+        writer.buffer.extend(&[0u8; 10][..]);
+
+        let mut stream = ChunkedStream { limit: 4 };
+        writer.write_to_stream(&mut stream).unwrap();
+
+        let writes = observer.writes.lock().unwrap();
+        assert_eq!(*writes, vec![(4, 4), (4, 8), (2, 10)]);
+        assert_eq!(writer.bytes_written(), 10);
+    }
+
+    #[test]
+    fn notify_state_only_fires_on_transition() {
+        let mut writer = HttpClientWriter::new(SharedBytes::default());
+        let observer = Arc::new(RecordingObserver::default());
+        writer.set_observer(Box::new(observer.clone()));
+
+        writer.notify_state(WriterState::Done);
+        writer.notify_state(WriterState::Done);
+        writer.notify_state(WriterState::Pause);
+        writer.notify_state(WriterState::Pause);
+        writer.notify_state(WriterState::Done);
+
+        let states = observer.states.lock().unwrap();
+        assert_eq!(*states, vec![WriterState::Done, WriterState::Pause, WriterState::Done]);
+    }
+
+    #[test]
+    fn date_header_is_canonical_gmt() {
+        let mut buf = BytesMut::new();
+        set_date(&mut buf);
+        let value = ::std::str::from_utf8(&buf).unwrap();
+        assert_eq!(value.len(), DATE_VALUE_LENGTH);
+        assert!(value.ends_with("GMT"));
+        assert!(value.as_bytes()[3..5] == *b", ");
+    }
+
+    #[test]
+    fn date_cache_interval_zero_recomputes_every_call() {
+        set_date_cache_interval(Duration::zero());
+
+        let mut first = BytesMut::new();
+        set_date(&mut first);
+        let now = time::get_time();
+        let mut second = BytesMut::new();
+        set_date(&mut second);
+
+        // with no caching, the value reflects `now` independently each call
+        CACHED.with(|cache| {
+            assert!(cache.borrow().next_update <= now);
+        });
+
+        set_date_cache_interval(Duration::seconds(1));
+    }
+}
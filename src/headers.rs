@@ -0,0 +1,37 @@
+/// Represents a supported content encoding.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContentEncoding {
+    /// Pick the most appropriate encoding automatically.
+    Auto,
+    /// Brotli encoding.
+    Br,
+    /// Gzip encoding.
+    Gzip,
+    /// Deflate encoding.
+    Deflate,
+    /// Zstandard encoding.
+    Zstd,
+    /// No compression.
+    Identity,
+}
+
+impl ContentEncoding {
+    #[inline]
+    pub fn is_compression(&self) -> bool {
+        match *self {
+            ContentEncoding::Identity | ContentEncoding::Auto => false,
+            _ => true,
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ContentEncoding::Br => "br",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Identity | ContentEncoding::Auto => "identity",
+        }
+    }
+}
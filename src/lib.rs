@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate bitflags;
+#[macro_use]
+extern crate log;
+extern crate brotli2;
+extern crate bytes;
+extern crate flate2;
+extern crate futures;
+extern crate http;
+extern crate time;
+extern crate tokio_io;
+extern crate zstd;
+
+pub mod body;
+pub mod client;
+pub mod headers;
+pub mod server;
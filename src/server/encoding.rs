@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+use std::mem;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use brotli2::write::BrotliEncoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+use server::shared::SharedBytes;
+
+/// Wraps a `SharedBytes` buffer and applies the chosen framing
+/// (`Content-Length`, `Transfer-Encoding: chunked`, or plain EOF-terminated).
+pub(crate) enum TransferEncoding {
+    Eof(SharedBytes),
+    Length(u64, SharedBytes),
+    Chunked(bool, SharedBytes),
+}
+
+impl TransferEncoding {
+    pub fn eof(buf: SharedBytes) -> TransferEncoding {
+        TransferEncoding::Eof(buf)
+    }
+
+    pub fn length(len: u64, buf: SharedBytes) -> TransferEncoding {
+        TransferEncoding::Length(len, buf)
+    }
+
+    pub fn chunked(buf: SharedBytes) -> TransferEncoding {
+        TransferEncoding::Chunked(false, buf)
+    }
+
+    pub fn is_eof(&self) -> bool {
+        match *self {
+            TransferEncoding::Eof(_) => true,
+            TransferEncoding::Length(len, _) => len == 0,
+            TransferEncoding::Chunked(eof, _) => eof,
+        }
+    }
+}
+
+impl Write for TransferEncoding {
+    fn write(&mut self, msg: &[u8]) -> io::Result<usize> {
+        match *self {
+            TransferEncoding::Eof(ref buf) => {
+                buf.extend(msg);
+            }
+            TransferEncoding::Length(ref mut remaining, ref buf) => {
+                if *remaining > 0 {
+                    let len = ::std::cmp::min(*remaining, msg.len() as u64);
+                    buf.extend(&msg[..len as usize]);
+                    *remaining -= len;
+                }
+            }
+            TransferEncoding::Chunked(ref mut eof, ref buf) => {
+                if *eof {
+                    return Ok(0);
+                }
+                if msg.is_empty() {
+                    *eof = true;
+                    buf.extend(b"0\r\n\r\n");
+                } else {
+                    buf.extend(format!("{:X}\r\n", msg.len()).into_bytes());
+                    buf.extend(msg);
+                    buf.extend(b"\r\n");
+                }
+            }
+        }
+        Ok(msg.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Applies a content-coding to body bytes as they're written to the
+/// underlying `TransferEncoding`.
+pub(crate) enum ContentEncoder {
+    Deflate(DeflateEncoder<TransferEncoding>),
+    Gzip(GzEncoder<TransferEncoding>),
+    Br(BrotliEncoder<TransferEncoding>),
+    Zstd(ZstdEncoder<TransferEncoding>),
+    Identity(TransferEncoding),
+}
+
+impl ContentEncoder {
+    pub fn is_eof(&self) -> bool {
+        match *self {
+            ContentEncoder::Deflate(ref encoder) => encoder.get_ref().is_eof(),
+            ContentEncoder::Gzip(ref encoder) => encoder.get_ref().is_eof(),
+            ContentEncoder::Br(ref encoder) => encoder.get_ref().is_eof(),
+            ContentEncoder::Zstd(ref encoder) => encoder.get_ref().is_eof(),
+            ContentEncoder::Identity(ref transfer) => transfer.is_eof(),
+        }
+    }
+
+    pub fn write_eof(&mut self) -> io::Result<()> {
+        let mut transfer = match *self {
+            ContentEncoder::Deflate(ref mut encoder) => {
+                mem::replace(encoder, DeflateEncoder::new(
+                    TransferEncoding::eof(SharedBytes::default()),
+                    ::flate2::Compression::default())).finish()?
+            }
+            ContentEncoder::Gzip(ref mut encoder) => {
+                mem::replace(encoder, GzEncoder::new(
+                    TransferEncoding::eof(SharedBytes::default()),
+                    ::flate2::Compression::default())).finish()?
+            }
+            ContentEncoder::Br(ref mut encoder) => {
+                mem::replace(encoder, BrotliEncoder::new(
+                    TransferEncoding::eof(SharedBytes::default()), 5)).finish()?
+            }
+            ContentEncoder::Zstd(ref mut encoder) => {
+                let dummy = ZstdEncoder::new(TransferEncoding::eof(SharedBytes::default()), 0)?;
+                mem::replace(encoder, dummy).finish()?
+            }
+            ContentEncoder::Identity(ref mut transfer) => {
+                transfer.write(&[])?;
+                return Ok(());
+            }
+        };
+        // signal end-of-body to the underlying framing (e.g. the chunked terminator)
+        transfer.write(&[])?;
+        Ok(())
+    }
+
+    pub fn write(&mut self, data: impl AsRef<[u8]>) -> io::Result<()> {
+        match *self {
+            ContentEncoder::Deflate(ref mut encoder) => {
+                encoder.write_all(data.as_ref())?;
+            }
+            ContentEncoder::Gzip(ref mut encoder) => {
+                encoder.write_all(data.as_ref())?;
+            }
+            ContentEncoder::Br(ref mut encoder) => {
+                encoder.write_all(data.as_ref())?;
+            }
+            ContentEncoder::Zstd(ref mut encoder) => {
+                encoder.write_all(data.as_ref())?;
+            }
+            ContentEncoder::Identity(ref mut transfer) => {
+                transfer.write_all(data.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+}
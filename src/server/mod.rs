@@ -0,0 +1,9 @@
+pub(crate) mod encoding;
+pub(crate) mod shared;
+
+/// Result of a single write attempt to the underlying stream.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WriterState {
+    Done,
+    Pause,
+}
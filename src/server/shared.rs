@@ -0,0 +1,74 @@
+use std::cell::UnsafeCell;
+use std::mem;
+use std::rc::Rc;
+
+use bytes::BytesMut;
+
+/// A reference-counted, single-threaded write buffer.
+///
+/// Connections are driven to completion on a single thread, so a plain
+/// `Rc<UnsafeCell<_>>` is used here instead of `Rc<RefCell<_>>` to avoid
+/// paying for runtime borrow checks on every write.
+#[derive(Debug)]
+pub(crate) struct SharedBytes(Rc<UnsafeCell<BytesMut>>);
+
+impl Default for SharedBytes {
+    fn default() -> SharedBytes {
+        SharedBytes(Rc::new(UnsafeCell::new(BytesMut::new())))
+    }
+}
+
+impl Clone for SharedBytes {
+    fn clone(&self) -> SharedBytes {
+        SharedBytes(Rc::clone(&self.0))
+    }
+}
+
+impl SharedBytes {
+    pub fn empty() -> SharedBytes {
+        SharedBytes::default()
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn get_mut_ref(&self) -> &mut BytesMut {
+        unsafe { &mut *self.0.get() }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &BytesMut {
+        unsafe { &*self.0.get() }
+    }
+
+    #[inline]
+    pub fn get_mut(&self) -> &mut BytesMut {
+        self.get_mut_ref()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.get_ref().is_empty()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.get_ref().len()
+    }
+
+    #[inline]
+    pub fn as_ref(&self) -> &[u8] {
+        self.get_ref().as_ref()
+    }
+
+    pub fn split_to(&self, n: usize) -> BytesMut {
+        self.get_mut_ref().split_to(n)
+    }
+
+    pub fn take(&self) -> BytesMut {
+        mem::replace(self.get_mut_ref(), BytesMut::new())
+    }
+
+    pub fn extend(&self, data: impl AsRef<[u8]>) {
+        self.get_mut_ref().extend_from_slice(data.as_ref());
+    }
+}